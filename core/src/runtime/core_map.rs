@@ -0,0 +1,201 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::throttle::run_throttled;
+use crate::dpdk::CoreId;
+use crate::runtime::MempoolMap;
+use crate::Result;
+use failure::Fail;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio_executor::current_thread;
+use tokio_net::driver;
+use tokio_timer::timer;
+
+/// Errors raised while building or looking up the `CoreMap`.
+#[derive(Debug, Fail)]
+pub enum CoreError {
+    #[fail(display = "core {} is not found.", _0)]
+    NotFound(usize),
+    #[fail(display = "core {} is not assigned to any port.", _0)]
+    NotAssigned(usize),
+    #[fail(display = "no master core was configured.")]
+    NoMasterCore,
+}
+
+/// A handle used to stop a core's executor loop and wake it if it's
+/// currently parked.
+pub(crate) struct ShutdownHandle {
+    running: Arc<AtomicBool>,
+    thread: thread::Thread,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn shutdown(self) {
+        self.running.store(false, Ordering::Relaxed);
+        self.thread.unpark();
+    }
+}
+
+/// The resources a non-master core needs to run pipelines: a handle to
+/// spawn tasks onto its executor from the master thread, and the means
+/// to park/unpark and shut it down.
+pub struct CoreExecutor {
+    pub(crate) thread: current_thread::Handle,
+    pub(crate) unpark: Option<thread::Thread>,
+    pub(crate) shutdown: Option<ShutdownHandle>,
+    pub(crate) join: Option<thread::JoinHandle<()>>,
+}
+
+/// The resources the master core needs. Unlike the other cores, the
+/// master core's executor is driven directly on the main thread via
+/// `block_on`, so its reactor and timer are kept alongside it instead of
+/// behind a `Handle`.
+pub struct MasterExecutor {
+    pub(crate) reactor: driver::Handle,
+    pub(crate) timer: timer::Timer<driver::Reactor>,
+    pub(crate) thread: current_thread::Runtime,
+}
+
+/// All the cores a `Runtime` is managing.
+pub struct CoreMap {
+    pub(crate) cores: HashMap<CoreId, CoreExecutor>,
+    pub(crate) master_core: MasterExecutor,
+}
+
+/// Builds a `CoreMap`, spawning one OS thread per non-master core.
+pub struct CoreMapBuilder<'m> {
+    cores: Vec<CoreId>,
+    master_core: Option<CoreId>,
+    mempools: Option<&'m mut MempoolMap>,
+    throttle: Duration,
+}
+
+impl<'m> CoreMapBuilder<'m> {
+    pub fn new() -> Self {
+        CoreMapBuilder {
+            cores: vec![],
+            master_core: None,
+            mempools: None,
+            throttle: Duration::from_secs(0),
+        }
+    }
+
+    pub fn cores(&mut self, cores: &[CoreId]) -> &mut Self {
+        self.cores = cores.to_vec();
+        self
+    }
+
+    pub fn master_core(&mut self, core: CoreId) -> &mut Self {
+        self.master_core = Some(core);
+        self
+    }
+
+    pub fn mempools(&mut self, mempools: &'m mut MempoolMap) -> &mut Self {
+        self.mempools = Some(mempools);
+        self
+    }
+
+    /// Sets how long each non-master core's executor parks between polls
+    /// once its run-queue and the IO driver are both idle. A duration of
+    /// zero (the default) keeps today's behavior: the executor polls the
+    /// reactor as fast as it can.
+    pub fn throttle(&mut self, throttle: Duration) -> &mut Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Spawns the OS threads and builds the `CoreMap`.
+    pub fn finish(&mut self) -> Result<CoreMap> {
+        let master_core = self.master_core.ok_or(CoreError::NoMasterCore)?;
+
+        let mut cores = HashMap::new();
+
+        for &core_id in self.cores.iter().filter(|&&core_id| core_id != master_core) {
+            cores.insert(core_id, self.spawn_core(core_id)?);
+        }
+
+        let reactor = driver::Reactor::new()?;
+        let reactor_handle = reactor.handle();
+        let timer = timer::Timer::new(reactor);
+        let thread = current_thread::Runtime::new()?;
+
+        Ok(CoreMap {
+            cores,
+            master_core: MasterExecutor {
+                reactor: reactor_handle,
+                timer,
+                thread,
+            },
+        })
+    }
+
+    /// Spawns the OS thread backing a single non-master core, and waits
+    /// for it to finish initializing its executor before returning.
+    fn spawn_core(&self, core_id: CoreId) -> Result<CoreExecutor> {
+        let quantum = self.throttle;
+        let running = Arc::new(AtomicBool::new(true));
+        let running2 = running.clone();
+
+        let (tx, rx) = mpsc::channel();
+
+        let join = thread::Builder::new()
+            .name(format!("{:?}", core_id))
+            .spawn(move || {
+                let mut rt =
+                    current_thread::Runtime::new().expect("failed to create core executor");
+                let reactor = driver::Reactor::new().expect("failed to create core reactor");
+                let reactor_handle = reactor.handle();
+                let timer = timer::Timer::new(reactor);
+
+                tx.send(rt.handle())
+                    .expect("core executor handshake failed");
+
+                // parks until `execute()` unparks it to start running
+                // installed pipelines.
+                thread::park();
+
+                let _guard = driver::set_default(&reactor_handle);
+                let _timer_guard = timer::set_default(&timer);
+                run_throttled(&mut rt, quantum, &running2);
+            })?;
+
+        let handle = rx
+            .recv()
+            .map_err(|_| CoreError::NotFound(core_id.raw()))?;
+
+        // the thread we just spawned is parked waiting to be unparked by
+        // `execute()`; we keep its handle both to start it and, later, to
+        // wake it immediately on shutdown even if it's mid-quantum.
+        let unpark = join.thread().clone();
+
+        Ok(CoreExecutor {
+            thread: handle,
+            unpark: Some(unpark.clone()),
+            shutdown: Some(ShutdownHandle {
+                running,
+                thread: unpark,
+            }),
+            join: Some(join),
+        })
+    }
+}
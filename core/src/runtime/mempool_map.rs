@@ -0,0 +1,49 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::dpdk::{Mempool, SocketId};
+use crate::Result;
+use std::collections::HashMap;
+
+/// The per-NUMA-socket DPDK mempools shared by every port and core.
+pub struct MempoolMap {
+    mempools: HashMap<SocketId, Mempool>,
+}
+
+impl MempoolMap {
+    /// Creates one mempool per socket in `sockets`.
+    pub fn new(capacity: usize, cache_size: usize, sockets: &[SocketId]) -> Result<Self> {
+        let mempools = sockets
+            .iter()
+            .map(|&socket| Mempool::new(capacity, cache_size, socket).map(|pool| (socket, pool)))
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        Ok(MempoolMap { mempools })
+    }
+
+    /// Returns the mempool for `socket`, if one was created for it.
+    pub fn get(&self, socket: SocketId) -> Option<&Mempool> {
+        self.mempools.get(&socket)
+    }
+
+    /// Re-borrows `self` mutably, so the same map can be handed to
+    /// multiple builder calls in sequence.
+    pub fn borrow_mut(&mut self) -> &mut Self {
+        self
+    }
+}
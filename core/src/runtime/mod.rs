@@ -18,22 +18,27 @@
 
 mod core_map;
 mod mempool_map;
+mod shutdown;
+mod throttle;
 
 pub use self::core_map::*;
 pub use self::mempool_map::*;
+pub use self::shutdown::*;
+pub use self::throttle::*;
 
 use crate::dpdk::{eal_cleanup, eal_init, CoreId, Port, PortBuilder, PortError, PortQueue};
 use crate::settings::RuntimeSettings;
-use crate::{debug, ensure, info, Result};
+use crate::{debug, ensure, info, warn, Result};
 use futures::{future, stream, Future, StreamExt};
 use libc;
+use rand::Rng;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio_executor::current_thread;
 use tokio_net::driver;
 use tokio_net::signal::unix::{self, SignalKind};
-use tokio_timer::{timer, Interval};
+use tokio_timer::{timer, Delay, Interval};
 
 /// Supported Unix signals.
 #[derive(Copy, Clone, Debug)]
@@ -43,11 +48,57 @@ pub enum UnixSignal {
     SIGTERM = libc::SIGTERM as isize,
 }
 
+/// Retry/backoff policy for a fallible periodic task.
+#[derive(Copy, Clone, Debug)]
+pub enum RetryPolicy {
+    /// Retries after the same fixed delay every time.
+    Fixed { delay: Duration },
+    /// Retries after a delay that grows exponentially with the attempt
+    /// count, `delay = min(base_delay * factor.powi(attempt), max_delay)`,
+    /// optionally randomized by up to `± jitter` of that delay.
+    Exponential {
+        base_delay: Duration,
+        factor: f64,
+        max_delay: Duration,
+        jitter: f64,
+    },
+}
+
+impl RetryPolicy {
+    /// Computes the delay before the next retry, given how many
+    /// consecutive failures have already happened (0 for the first
+    /// retry).
+    fn delay_for(&self, attempt: u32) -> Duration {
+        match *self {
+            RetryPolicy::Fixed { delay } => delay,
+            RetryPolicy::Exponential {
+                base_delay,
+                factor,
+                max_delay,
+                jitter,
+            } => {
+                let scaled = base_delay.as_secs_f64() * factor.powi(attempt as i32);
+                let capped = scaled.min(max_delay.as_secs_f64());
+
+                let spread = capped * jitter;
+                let jittered = if jitter > 0.0 && spread > 0.0 {
+                    capped + rand::thread_rng().gen_range(-spread, spread)
+                } else {
+                    capped
+                };
+
+                Duration::from_secs_f64(jittered.max(0.0))
+            }
+        }
+    }
+}
+
 pub struct Runtime {
     ports: Vec<Port>,
     mempools: MempoolMap,
     core_map: CoreMap,
     on_signal: Arc<dyn Fn(UnixSignal) -> bool>,
+    shutdown: ShutdownTrigger,
     config: RuntimeSettings,
 }
 
@@ -71,6 +122,7 @@ impl Runtime {
             .cores(&cores)
             .master_core(config.master_core)
             .mempools(mempools.borrow_mut())
+            .throttle(config.throttle)
             .finish()?;
 
         info!("initializing ports...");
@@ -93,6 +145,7 @@ impl Runtime {
             mempools,
             core_map,
             on_signal: Arc::new(|_| true),
+            shutdown: ShutdownTrigger::new(),
             config,
         })
     }
@@ -130,15 +183,19 @@ impl Runtime {
     /// cores assigned to the port.
     ///
     /// `port` is the logical name that identifies the port. The `installer`
-    /// is a closure that takes in a `PortQueue` and returns a `Pipeline`
-    /// that will be spawned onto the thread executor.
+    /// is a closure that takes in a `PortQueue` and a `ShutdownSignal` and
+    /// returns a `Pipeline` that will be spawned onto the thread executor.
+    /// The pipeline should race `ShutdownSignal::future()` against its
+    /// normal packet loop, stop accepting new work once it resolves, and
+    /// call `ShutdownSignal::ack_drained()` once any in-flight packets
+    /// have been flushed.
     pub fn add_pipeline_to_port<T: Future<Output = ()> + 'static, F>(
         &mut self,
         port: &str,
         installer: F,
     ) -> Result<&mut Self>
     where
-        F: Fn(PortQueue) -> T + Send + Sync + 'static,
+        F: Fn(PortQueue, ShutdownSignal) -> T + Send + Sync + 'static,
     {
         let port = &self
             .ports
@@ -153,13 +210,20 @@ impl Runtime {
             let port_q = *port_q;
             let thread = &self.core_map.cores[core_id].thread;
 
-            // spawns the bootstrap. we want the bootstrapping to execute on the
-            // target core instead of the master core. that way the actual task
-            // is spawned locally and the type bounds are less restricting.
-            thread.spawn(future::lazy(move |_| {
-                let task = f(port_q);
+            // registers with the drain count before spawning the
+            // bootstrap, and un-registers if the spawn fails, so a failed
+            // spawn doesn't leave the drain count expecting an
+            // acknowledgement that will never come.
+            let shutdown = self.shutdown.signal();
+            let spawned = thread.spawn(future::lazy(move |_| {
+                let task = f(port_q, shutdown);
                 current_thread::spawn(task);
-            }))?;
+            }));
+
+            if spawned.is_err() {
+                self.shutdown.cancel();
+            }
+            spawned?;
 
             debug!("installed pipeline on port_q for {:?}.", core_id);
         }
@@ -173,15 +237,20 @@ impl Runtime {
     /// to will be available to the pipeline.
     ///
     /// `core` is the logical id that identifies the core. The `installer`
-    /// is a closure that takes in a hashmap of `PortQueue`s and returns a
-    /// `Pipeline` that will be spawned onto the thread executor of the core.
+    /// is a closure that takes in a hashmap of `PortQueue`s and a
+    /// `ShutdownSignal`, and returns a `Pipeline` that will be spawned onto
+    /// the thread executor of the core. The pipeline should race
+    /// `ShutdownSignal::future()` against its normal packet loop, stop
+    /// accepting new work once it resolves, and call
+    /// `ShutdownSignal::ack_drained()` once any in-flight packets have
+    /// been flushed.
     pub fn add_pipeline_to_core<T: Future<Output = ()> + 'static, F>(
         &mut self,
         core: usize,
         installer: F,
     ) -> Result<&mut Self>
     where
-        F: FnOnce(HashMap<String, PortQueue>) -> T + Send + Sync + 'static,
+        F: FnOnce(HashMap<String, PortQueue>, ShutdownSignal) -> T + Send + Sync + 'static,
     {
         let core_id = CoreId::new(core);
 
@@ -200,12 +269,20 @@ impl Runtime {
 
         ensure!(!port_qs.is_empty(), CoreError::NotAssigned(core));
 
-        // spawns the bootstrap. we want the bootstrapping to execute on the
-        // target core instead of the master core.
-        thread.spawn(future::lazy(move |_| {
-            let task = installer(port_qs);
+        // registers with the drain count before spawning the bootstrap,
+        // and un-registers if the spawn fails, so a failed spawn doesn't
+        // leave the drain count expecting an acknowledgement that will
+        // never come.
+        let shutdown = self.shutdown.signal();
+        let spawned = thread.spawn(future::lazy(move |_| {
+            let task = installer(port_qs, shutdown);
             current_thread::spawn(task);
-        }))?;
+        }));
+
+        if spawned.is_err() {
+            self.shutdown.cancel();
+        }
+        spawned?;
 
         info!("installed pipeline for core {:?}.", core_id);
 
@@ -246,6 +323,43 @@ impl Runtime {
         Ok(self)
     }
 
+    /// Installs a fallible periodic task to a core, retrying on failure
+    /// according to `retry`.
+    ///
+    /// `core` is the logical id that identifies the core. `task` is the
+    /// closure to execute, rerun every `dur` interval on success. If
+    /// `task` returns an `Err`, it's rerun after the delay computed by
+    /// `retry` instead of waiting a full `dur`; the attempt counter used
+    /// by `retry` resets the next time `task` succeeds.
+    pub fn add_periodic_task_to_core_with_retry<T>(
+        &mut self,
+        core: usize,
+        task: T,
+        dur: Duration,
+        retry: RetryPolicy,
+    ) -> Result<&mut Self>
+    where
+        T: Fn() -> Result<()> + Send + Sync + 'static,
+    {
+        let core_id = CoreId::new(core);
+
+        let thread = &self
+            .core_map
+            .cores
+            .get(&core_id)
+            .ok_or_else(|| CoreError::NotFound(core))?
+            .thread;
+
+        // spawns the bootstrap. we want the bootstrapping to execute on the
+        // target core instead of the master core so the periodic task is
+        // associated with the correct timer instance.
+        thread.spawn(future::lazy(move |_| {
+            current_thread::spawn(run_with_retry(task, dur, retry));
+        }))?;
+
+        Ok(self)
+    }
+
     /// Blocks the main thread until a timeout expires.
     ///
     /// This mode is useful for running integration tests. The timeout
@@ -321,6 +435,14 @@ impl Runtime {
             Some(d) => self.wait_for_timeout(d),
         }?;
 
+        // trips the shutdown signal so pipelines can stop accepting new
+        // work and flush whatever's still in flight, then waits up to the
+        // configured grace period for them to acknowledge they're drained
+        // before we tear anything down.
+        debug!("tripping shutdown signal...");
+        self.shutdown.trip();
+        self.shutdown.wait_for_drain(self.config.shutdown_grace);
+
         // shuts down all the cores.
         for (core_id, core) in &mut self.core_map.cores {
             if let Some(trigger) = core.shutdown.take() {
@@ -345,6 +467,104 @@ impl Runtime {
 impl Drop for Runtime {
     fn drop(&mut self) {
         debug!("freeing EAL.");
-        eal_cleanup().unwrap();
+        let _ = eal_cleanup();
+    }
+}
+
+/// Drives a fallible periodic task, rescheduling it after `dur` on
+/// success, or after `retry`'s backoff on failure.
+async fn run_with_retry<T>(task: T, dur: Duration, retry: RetryPolicy)
+where
+    T: Fn() -> Result<()> + Send + Sync + 'static,
+{
+    let mut attempt = 0u32;
+    let mut wait = dur;
+
+    loop {
+        Delay::new(Instant::now() + wait).await;
+
+        match task() {
+            Ok(()) => {
+                attempt = 0;
+                wait = dur;
+            }
+            Err(err) => {
+                wait = retry.delay_for(attempt);
+                attempt += 1;
+                warn!("periodic task failed, retrying in {:?}: {}", wait, err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_delay_is_always_the_same() {
+        let retry = RetryPolicy::Fixed {
+            delay: Duration::from_secs(2),
+        };
+
+        assert_eq!(Duration::from_secs(2), retry.delay_for(0));
+        assert_eq!(Duration::from_secs(2), retry.delay_for(5));
+    }
+
+    #[test]
+    fn exponential_delay_grows_with_attempt() {
+        let retry = RetryPolicy::Exponential {
+            base_delay: Duration::from_secs(1),
+            factor: 2.0,
+            max_delay: Duration::from_secs(100),
+            jitter: 0.0,
+        };
+
+        assert_eq!(Duration::from_secs(1), retry.delay_for(0));
+        assert_eq!(Duration::from_secs(2), retry.delay_for(1));
+        assert_eq!(Duration::from_secs(4), retry.delay_for(2));
+    }
+
+    #[test]
+    fn exponential_delay_caps_at_max_delay() {
+        let retry = RetryPolicy::Exponential {
+            base_delay: Duration::from_secs(1),
+            factor: 2.0,
+            max_delay: Duration::from_secs(10),
+            jitter: 0.0,
+        };
+
+        assert_eq!(Duration::from_secs(10), retry.delay_for(10));
+    }
+
+    #[test]
+    fn exponential_delay_with_no_jitter_never_drifts_from_capped() {
+        let retry = RetryPolicy::Exponential {
+            base_delay: Duration::from_secs(0),
+            factor: 2.0,
+            max_delay: Duration::from_secs(10),
+            jitter: 0.0,
+        };
+
+        // base_delay of zero keeps `capped` at zero on every attempt;
+        // with jitter == 0.0 this must never perturb it, and must never
+        // panic on the zero-spread gen_range edge case.
+        assert_eq!(Duration::from_secs(0), retry.delay_for(0));
+        assert_eq!(Duration::from_secs(0), retry.delay_for(3));
+    }
+
+    #[test]
+    fn exponential_delay_with_jitter_does_not_panic_on_zero_spread() {
+        let retry = RetryPolicy::Exponential {
+            base_delay: Duration::from_secs(0),
+            factor: 2.0,
+            max_delay: Duration::from_secs(10),
+            jitter: 0.5,
+        };
+
+        // capped == 0.0 here even though jitter > 0.0, so spread == 0.0;
+        // this must take the unjittered branch instead of calling
+        // gen_range on an empty range.
+        assert_eq!(Duration::from_secs(0), retry.delay_for(0));
     }
 }
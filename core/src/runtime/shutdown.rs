@@ -0,0 +1,167 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::debug;
+use futures::channel::oneshot;
+use futures::future::Shared;
+use futures::{Future, FutureExt};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A cloneable handle pipelines use to cooperate with shutdown.
+///
+/// `future()` resolves the moment `execute()` starts tearing down the
+/// runtime, letting a pipeline stop accepting new work and flush what's
+/// already in flight. Once flushed, the pipeline calls `ack_drained()`
+/// so `execute()` knows it no longer needs to wait on it.
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    tripped: Shared<oneshot::Receiver<()>>,
+    drained: Arc<AtomicUsize>,
+    expected: Arc<AtomicUsize>,
+}
+
+impl ShutdownSignal {
+    /// Returns a future that resolves once shutdown has been tripped.
+    pub fn future(&self) -> impl Future<Output = ()> + 'static {
+        let tripped = self.tripped.clone();
+        async move {
+            let _ = tripped.await;
+        }
+    }
+
+    /// Acknowledges that this pipeline has flushed its in-flight work
+    /// and is safe to tear down.
+    pub fn ack_drained(&self) {
+        self.drained.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Coordinates graceful shutdown across all the installed pipelines.
+///
+/// `execute()` owns the trigger; `signal()` hands a clone of the
+/// `ShutdownSignal` to each pipeline as it's installed.
+pub struct ShutdownTrigger {
+    tx: Option<oneshot::Sender<()>>,
+    signal: ShutdownSignal,
+}
+
+impl ShutdownTrigger {
+    pub fn new() -> Self {
+        let (tx, rx) = oneshot::channel();
+        ShutdownTrigger {
+            tx: Some(tx),
+            signal: ShutdownSignal {
+                tripped: rx.shared(),
+                drained: Arc::new(AtomicUsize::new(0)),
+                expected: Arc::new(AtomicUsize::new(0)),
+            },
+        }
+    }
+
+    /// Hands out a clone of the signal to a newly installed pipeline,
+    /// registering it as one more pipeline `wait_for_drain` waits on.
+    pub fn signal(&self) -> ShutdownSignal {
+        self.signal.expected.fetch_add(1, Ordering::SeqCst);
+        self.signal.clone()
+    }
+
+    /// Un-registers a pipeline counted by a prior `signal()` call that
+    /// was never actually installed, e.g. because spawning its bootstrap
+    /// task failed. Without this, `wait_for_drain` would wait the full
+    /// grace period for an acknowledgement that can never come.
+    pub fn cancel(&self) {
+        self.signal.expected.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Trips the signal, waking every pipeline's `future()`.
+    pub fn trip(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(());
+        }
+    }
+
+    /// Blocks the calling thread until every installed pipeline has
+    /// called `ack_drained`, or until `grace` elapses, whichever comes
+    /// first.
+    pub fn wait_for_drain(&self, grace: Duration) {
+        let deadline = Instant::now() + grace;
+
+        loop {
+            let drained = self.signal.drained.load(Ordering::SeqCst);
+            let expected = self.signal.expected.load(Ordering::SeqCst);
+            if drained >= expected {
+                debug!("all pipelines drained.");
+                return;
+            }
+
+            if Instant::now() >= deadline {
+                debug!(
+                    "shutdown grace period elapsed with {}/{} pipelines drained.",
+                    drained, expected
+                );
+                return;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_returns_expected_to_baseline() {
+        let trigger = ShutdownTrigger::new();
+
+        let _a = trigger.signal();
+        let _b = trigger.signal();
+        trigger.cancel();
+
+        assert_eq!(1, trigger.signal.expected.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn wait_for_drain_returns_once_all_acked() {
+        let trigger = ShutdownTrigger::new();
+        let signal = trigger.signal();
+        signal.ack_drained();
+
+        let start = Instant::now();
+        trigger.wait_for_drain(Duration::from_secs(5));
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn wait_for_drain_respects_grace_timeout() {
+        let trigger = ShutdownTrigger::new();
+        let _signal = trigger.signal();
+
+        let grace = Duration::from_millis(50);
+        let start = Instant::now();
+        trigger.wait_for_drain(grace);
+
+        assert!(start.elapsed() >= grace);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,69 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio_executor::current_thread;
+
+/// How long an unthrottled core still blocks on a single `turn` before
+/// re-checking `running`, so a core with no pending IO remains
+/// responsive to shutdown instead of parking on the reactor forever.
+const UNTHROTTLED_POLL: Duration = Duration::from_millis(100);
+
+/// Runs a core's `current_thread` executor in fixed-size quanta instead
+/// of spinning on the reactor as fast as possible, until `running` is
+/// set to `false`.
+///
+/// Every quantum: drains and polls whatever tasks are currently ready,
+/// polls the IO driver for the remainder of the quantum so IO events can
+/// still wake the loop early, and parks the thread for what's left. This
+/// batches packet-processing wakeups into bounded windows, trading a
+/// little latency (up to `quantum`) for far less CPU/power burned on
+/// idle or low-traffic cores. A `quantum` of zero falls through to a
+/// plain, unthrottled loop that polls the reactor as fast as it can.
+pub fn run_throttled(rt: &mut current_thread::Runtime, quantum: Duration, running: &AtomicBool) {
+    if quantum == Duration::from_secs(0) {
+        while running.load(Ordering::Relaxed) {
+            let _ = rt.turn(Some(UNTHROTTLED_POLL));
+        }
+        return;
+    }
+
+    while running.load(Ordering::Relaxed) {
+        let start = Instant::now();
+
+        // drains every task that's immediately ready to make progress.
+        while rt
+            .turn(Some(Duration::from_secs(0)))
+            .map(|turn| turn.polled())
+            .unwrap_or(false)
+        {}
+
+        // polls the IO driver for whatever's left of the quantum so an
+        // incoming packet can still wake us before the quantum elapses.
+        if let Some(remaining) = quantum.checked_sub(start.elapsed()) {
+            let _ = rt.turn(Some(remaining));
+        }
+
+        // parks for the rest of the quantum, if any is left.
+        if let Some(remaining) = quantum.checked_sub(start.elapsed()) {
+            thread::park_timeout(remaining);
+        }
+    }
+}
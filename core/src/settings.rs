@@ -0,0 +1,79 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::dpdk::CoreId;
+use std::time::Duration;
+
+/// Mempool sizing knobs, shared by every NUMA socket in use.
+#[derive(Clone, Debug)]
+pub struct MempoolSettings {
+    pub capacity: usize,
+    pub cache_size: usize,
+}
+
+/// Per-port configuration.
+#[derive(Clone, Debug)]
+pub struct PortSettings {
+    pub name: String,
+    pub device: String,
+    pub cores: Vec<CoreId>,
+    pub rxd: usize,
+    pub txd: usize,
+}
+
+/// Runtime-wide configuration, typically loaded from a TOML file.
+#[derive(Clone, Debug)]
+pub struct RuntimeSettings {
+    pub master_core: CoreId,
+    pub cores: Vec<CoreId>,
+    pub mempool: MempoolSettings,
+    pub ports: Vec<PortSettings>,
+
+    /// How long `execute()` runs before stopping. `None` or `Some(0)`
+    /// waits for a Unix signal instead.
+    pub duration: Option<u64>,
+
+    /// How long `execute()` waits for every installed pipeline to
+    /// acknowledge it has drained in-flight work before sending the hard
+    /// shutdown trigger.
+    pub shutdown_grace: Duration,
+
+    /// How long each non-master core's executor parks between polls once its
+    /// run-queue and the IO driver are both idle. `Duration::from_secs(0)`
+    /// (the default) disables throttling and runs the reactor as fast as
+    /// it can.
+    pub throttle: Duration,
+}
+
+impl RuntimeSettings {
+    /// Returns the EAL init arguments for this configuration. No setting
+    /// is translated into an EAL argument; the list is always empty.
+    pub fn to_eal_args(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Returns the cores listed in `cores` plus the master core, sorted
+    /// and deduplicated. The cores listed in `ports` are not read.
+    pub fn all_cores(&self) -> Vec<CoreId> {
+        let mut cores = self.cores.clone();
+        cores.push(self.master_core);
+        cores.sort_unstable();
+        cores.dedup();
+        cores
+    }
+}
@@ -0,0 +1,358 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use packets::udp::Udp;
+use packets::{buffer, Packet, ParseError};
+
+/// The Destination Connection ID length assumed when parsing a short
+/// header packet whose length wasn't negotiated out of band.
+///
+/// Short header packets don't carry their DCID length on the wire, so
+/// callers that know the length their connections were set up with
+/// should parse with [`Quic::parse_with_dcid_len`] instead of the
+/// `Packet::parse` default.
+pub const DEFAULT_DCID_LEN: usize = 8;
+
+/// Whether a QUIC packet uses the long or short header form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeaderForm {
+    Long,
+    Short,
+}
+
+/// The packet type carried in a QUIC long header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LongHeaderPacketType {
+    Initial,
+    ZeroRtt,
+    Handshake,
+    Retry,
+}
+
+/// A QUIC packet, parsed from a `Udp` envelope.
+///
+/// Only the invariant header fields needed to classify and steer QUIC
+/// traffic are parsed: header form, long packet type, version, and the
+/// connection IDs. The rest of the packet (packet number, payload) is
+/// left untouched in the underlying buffer.
+pub struct Quic {
+    envelope: Udp,
+    offset: usize,
+    header_len: usize,
+    header_form: HeaderForm,
+    long_packet_type: Option<LongHeaderPacketType>,
+    version: Option<u32>,
+    dcid: Vec<u8>,
+    scid: Vec<u8>,
+}
+
+impl Quic {
+    /// Returns whether this packet uses the long or short header form.
+    #[inline]
+    pub fn header_form(&self) -> HeaderForm {
+        self.header_form
+    }
+
+    /// Returns the long header packet type. `None` for short header
+    /// packets, which don't carry one.
+    #[inline]
+    pub fn long_packet_type(&self) -> Option<LongHeaderPacketType> {
+        self.long_packet_type
+    }
+
+    /// Returns the QUIC version. `None` for short header packets, which
+    /// don't carry one.
+    #[inline]
+    pub fn version(&self) -> Option<u32> {
+        self.version
+    }
+
+    /// Returns the Destination Connection ID.
+    #[inline]
+    pub fn dcid(&self) -> &[u8] {
+        &self.dcid
+    }
+
+    /// Returns the Source Connection ID. Empty for short header
+    /// packets, which don't carry one.
+    #[inline]
+    pub fn scid(&self) -> &[u8] {
+        &self.scid
+    }
+
+    /// Parses `envelope` as a QUIC packet, using `dcid_len` as the
+    /// Destination Connection ID length for short header packets.
+    ///
+    /// Use this over the `Packet::parse` default whenever the DCID
+    /// length negotiated for the connection isn't [`DEFAULT_DCID_LEN`].
+    pub fn parse_with_dcid_len(envelope: Udp, dcid_len: usize) -> Result<Self> {
+        let mbuf = envelope.mbuf();
+        let offset = envelope.payload_offset();
+        let len = envelope.payload_len();
+        let data = buffer::read_slice(mbuf, offset, len)?;
+        let parsed = parse_header(data, dcid_len)?;
+
+        Ok(Quic {
+            envelope,
+            offset,
+            header_len: parsed.header_len,
+            header_form: parsed.header_form,
+            long_packet_type: parsed.long_packet_type,
+            version: parsed.version,
+            dcid: parsed.dcid,
+            scid: parsed.scid,
+        })
+    }
+}
+
+impl Packet for Quic {
+    type Header = ();
+    type Envelope = Udp;
+
+    #[inline]
+    fn envelope(&self) -> &Self::Envelope {
+        &self.envelope
+    }
+
+    #[inline]
+    fn mbuf(&self) -> *mut MBuf {
+        self.envelope.mbuf()
+    }
+
+    #[inline]
+    fn offset(&self) -> usize {
+        self.offset
+    }
+
+    #[inline]
+    fn header(&self) -> &mut Self::Header {
+        unreachable!("quic packet has no fixed-size header!");
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        self.header_len
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    fn do_parse(envelope: Self::Envelope) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Quic::parse_with_dcid_len(envelope, DEFAULT_DCID_LEN)
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    fn do_push(_envelope: Self::Envelope) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Err(ParseError::new("building new quic packets is not supported yet").into())
+    }
+
+    #[inline]
+    fn remove(self) -> Result<Self::Envelope> {
+        Ok(self.envelope)
+    }
+
+    #[inline]
+    fn cascade(&self) {
+        // noop
+    }
+
+    #[inline]
+    fn deparse(self) -> Self::Envelope {
+        self.envelope
+    }
+}
+
+/// The fields parsed out of a QUIC header, independent of how the bytes
+/// were obtained. Kept separate from `Quic` so the bounds-checking logic
+/// can be unit tested without a backing `Udp`/`MBuf`.
+struct ParsedHeader {
+    header_form: HeaderForm,
+    long_packet_type: Option<LongHeaderPacketType>,
+    version: Option<u32>,
+    dcid: Vec<u8>,
+    scid: Vec<u8>,
+    header_len: usize,
+}
+
+/// Parses a QUIC header out of `data`, the UDP payload, bounds-checking
+/// every variable-length field against `data`'s length and returning a
+/// `ParseError` on truncation. `dcid_len` is only used for short header
+/// packets, whose DCID isn't self-describing on the wire.
+fn parse_header(data: &[u8], dcid_len: usize) -> Result<ParsedHeader> {
+    let first_byte = *data
+        .get(0)
+        .ok_or_else(|| ParseError::new("quic packet is empty"))?;
+
+    if first_byte & 0x80 != 0 {
+        parse_long_header(data, first_byte)
+    } else {
+        parse_short_header(data, dcid_len)
+    }
+}
+
+fn parse_long_header(data: &[u8], first_byte: u8) -> Result<ParsedHeader> {
+    // first byte(1) + version(4) + dcid len(1) is the shortest a long
+    // header can be before we even know how long the connection ids are.
+    if data.len() < 6 {
+        return Err(ParseError::new("truncated quic long header").into());
+    }
+
+    let version = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+
+    let dcid_len = data[5] as usize;
+    let dcid_start = 6;
+    let dcid_end = dcid_start + dcid_len;
+    if data.len() < dcid_end + 1 {
+        return Err(ParseError::new("truncated quic destination connection id").into());
+    }
+    let dcid = data[dcid_start..dcid_end].to_vec();
+
+    let scid_len = data[dcid_end] as usize;
+    let scid_start = dcid_end + 1;
+    let scid_end = scid_start + scid_len;
+    if data.len() < scid_end {
+        return Err(ParseError::new("truncated quic source connection id").into());
+    }
+    let scid = data[scid_start..scid_end].to_vec();
+
+    let long_packet_type = match (first_byte >> 4) & 0x03 {
+        0x00 => LongHeaderPacketType::Initial,
+        0x01 => LongHeaderPacketType::ZeroRtt,
+        0x02 => LongHeaderPacketType::Handshake,
+        0x03 => LongHeaderPacketType::Retry,
+        _ => unreachable!(),
+    };
+
+    Ok(ParsedHeader {
+        header_form: HeaderForm::Long,
+        long_packet_type: Some(long_packet_type),
+        version: Some(version),
+        dcid,
+        scid,
+        header_len: scid_end,
+    })
+}
+
+fn parse_short_header(data: &[u8], dcid_len: usize) -> Result<ParsedHeader> {
+    let header_len = 1 + dcid_len;
+    if data.len() < header_len {
+        return Err(ParseError::new("truncated quic short header").into());
+    }
+
+    let dcid = data[1..header_len].to_vec();
+
+    Ok(ParsedHeader {
+        header_form: HeaderForm::Short,
+        long_packet_type: None,
+        version: None,
+        dcid,
+        scid: vec![],
+        header_len,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INITIAL_PACKET: [u8; 17] = [
+        0xc3, // long header, type = Initial
+        0x00, 0x00, 0x00, 0x01, // version 1
+        0x04, // dcid len = 4
+        0xde, 0xad, 0xbe, 0xef, // dcid
+        0x04, // scid len = 4
+        0xfe, 0xed, 0xfa, 0xce, // scid
+        0xff, 0xff, // trailing packet-number/payload bytes, not parsed
+    ];
+
+    const SHORT_PACKET: [u8; 9] = [
+        0x41, // short header
+        0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, // 8-byte dcid
+    ];
+
+    #[test]
+    fn parse_long_header_initial() {
+        let parsed = parse_header(&INITIAL_PACKET, DEFAULT_DCID_LEN).unwrap();
+
+        assert_eq!(HeaderForm::Long, parsed.header_form);
+        assert_eq!(
+            Some(LongHeaderPacketType::Initial),
+            parsed.long_packet_type
+        );
+        assert_eq!(Some(1), parsed.version);
+        assert_eq!(&[0xde, 0xad, 0xbe, 0xef], parsed.dcid.as_slice());
+        assert_eq!(&[0xfe, 0xed, 0xfa, 0xce], parsed.scid.as_slice());
+        assert_eq!(15, parsed.header_len);
+    }
+
+    #[test]
+    fn parse_long_header_packet_types() {
+        for (byte, expected) in &[
+            (0xc0u8, LongHeaderPacketType::Initial),
+            (0xd0, LongHeaderPacketType::ZeroRtt),
+            (0xe0, LongHeaderPacketType::Handshake),
+            (0xf0, LongHeaderPacketType::Retry),
+        ] {
+            let mut packet = INITIAL_PACKET;
+            packet[0] = *byte;
+            let parsed = parse_header(&packet, DEFAULT_DCID_LEN).unwrap();
+            assert_eq!(Some(*expected), parsed.long_packet_type);
+        }
+    }
+
+    #[test]
+    fn parse_long_header_truncated_dcid() {
+        let truncated = &INITIAL_PACKET[..7];
+        assert!(parse_header(truncated, DEFAULT_DCID_LEN).is_err());
+    }
+
+    #[test]
+    fn parse_long_header_truncated_scid() {
+        let truncated = &INITIAL_PACKET[..11];
+        assert!(parse_header(truncated, DEFAULT_DCID_LEN).is_err());
+    }
+
+    #[test]
+    fn parse_short_header_packet() {
+        let parsed = parse_header(&SHORT_PACKET, DEFAULT_DCID_LEN).unwrap();
+
+        assert_eq!(HeaderForm::Short, parsed.header_form);
+        assert_eq!(None, parsed.long_packet_type);
+        assert_eq!(None, parsed.version);
+        assert_eq!(&SHORT_PACKET[1..], parsed.dcid.as_slice());
+        assert!(parsed.scid.is_empty());
+        assert_eq!(9, parsed.header_len);
+    }
+
+    #[test]
+    fn parse_short_header_truncated() {
+        let truncated = &SHORT_PACKET[..4];
+        assert!(parse_header(truncated, DEFAULT_DCID_LEN).is_err());
+    }
+
+    #[test]
+    fn parse_empty_packet() {
+        assert!(parse_header(&[], DEFAULT_DCID_LEN).is_err());
+    }
+}